@@ -0,0 +1,249 @@
+use crate::{
+    chunk::{layer::DenseLayer, raw_tile::RawTile},
+    lib::*,
+};
+use rand::Rng;
+
+/// How far a jittered point may be nudged from its template position, in tiles.
+const JITTER_RANGE: i32 = 1;
+
+/// The footprint, in tiles, of a generated map.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Size {
+    /// Width in tiles.
+    pub width: u32,
+    /// Height in tiles.
+    pub height: u32,
+}
+
+/// A point in tile coordinates.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Point {
+    /// The x coordinate.
+    pub x: i32,
+    /// The y coordinate.
+    pub y: i32,
+}
+
+/// One outlined region of a template: a boundary polygon, a point known to
+/// be inside it, and the sprite-sheet index tiles in the region should use.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, PartialEq, Debug)]
+pub struct TemplateRegion {
+    /// The polygon's vertices, in tile coordinates, wound in order.
+    pub outline: Vec<Point>,
+    /// A point inside the outline used to seed the region's flood fill.
+    pub fill_point: Point,
+    /// The sprite-sheet index stamped onto every tile in the region.
+    pub index: usize,
+}
+
+/// A declarative description of a procedurally generated map, loadable from
+/// YAML or RON.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, PartialEq, Debug)]
+pub struct Template {
+    /// The generated map's footprint.
+    pub size: Size,
+    /// The regions to rasterize and fill, in order.
+    pub regions: Vec<TemplateRegion>,
+}
+
+/// Picks a template at random and generates a dense layer from it, or
+/// `None` if `templates` is empty.
+///
+/// The same `rng` seed always selects the same template and jitters its
+/// points the same way, so generation is reproducible.
+pub fn generate<R: Rng>(templates: &[Template], rng: &mut R) -> Option<DenseLayer<RawTile>> {
+    if templates.is_empty() {
+        return None;
+    }
+    let template = &templates[rng.gen_range(0..templates.len())];
+    Some(generate_from_template(template, rng))
+}
+
+/// Generates a dense layer from a single template by rasterizing each
+/// region's outline and flood-filling its interior.
+pub fn generate_from_template<R: Rng>(template: &Template, rng: &mut R) -> DenseLayer<RawTile> {
+    let area = (template.size.width * template.size.height) as usize;
+    let mut tiles = vec![RawTile::default(); area];
+    for tile in tiles.iter_mut() {
+        tile.hide();
+    }
+    for region in &template.regions {
+        // Jitter the whole region by a single offset so the outline and its
+        // fill point move together: the fill point stays interior no matter
+        // how far it's nudged.
+        let offset = jitter_offset(rng);
+        let outline: Vec<Point> = region
+            .outline
+            .iter()
+            .map(|point| translate(*point, offset))
+            .collect();
+        let fill_point = translate(region.fill_point, offset);
+        rasterize_outline(&outline, template.size, region.index, &mut tiles);
+        flood_fill(fill_point, template.size, region.index, &mut tiles);
+    }
+    DenseLayer::new(tiles)
+}
+
+/// Draws a single `(dx, dy)` offset, up to `JITTER_RANGE` tiles per axis,
+/// deterministically with respect to `rng`.
+fn jitter_offset<R: Rng>(rng: &mut R) -> Point {
+    Point {
+        x: rng.gen_range(-JITTER_RANGE..=JITTER_RANGE),
+        y: rng.gen_range(-JITTER_RANGE..=JITTER_RANGE),
+    }
+}
+
+/// Shifts a point by an offset.
+fn translate(point: Point, offset: Point) -> Point {
+    Point {
+        x: point.x + offset.x,
+        y: point.y + offset.y,
+    }
+}
+
+/// Converts a tile coordinate into a flat `Layer` index, or `None` if it
+/// falls outside `size`.
+fn cell_index(point: Point, size: Size) -> Option<usize> {
+    if point.x < 0 || point.y < 0 {
+        return None;
+    }
+    let (x, y) = (point.x as u32, point.y as u32);
+    if x >= size.width || y >= size.height {
+        return None;
+    }
+    Some((y * size.width + x) as usize)
+}
+
+/// Draws a closed polygon's edges into `tiles`, stamping `index` along the
+/// way. The painted cells double as flood-fill boundaries.
+fn rasterize_outline(outline: &[Point], size: Size, index: usize, tiles: &mut [RawTile]) {
+    if outline.len() < 2 {
+        return;
+    }
+    for i in 0..outline.len() {
+        draw_line(outline[i], outline[(i + 1) % outline.len()], size, index, tiles);
+    }
+}
+
+/// Stamps `index` along the line from `a` to `b` using Bresenham's algorithm.
+fn draw_line(a: Point, b: Point, size: Size, index: usize, tiles: &mut [RawTile]) {
+    let (mut x0, mut y0) = (a.x, a.y);
+    let (x1, y1) = (b.x, b.y);
+    let dx = (x1 - x0).abs();
+    let dy = -(y1 - y0).abs();
+    let sx = if x0 < x1 { 1 } else { -1 };
+    let sy = if y0 < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
+    loop {
+        if let Some(i) = cell_index(Point { x: x0, y: y0 }, size) {
+            tiles[i] = RawTile {
+                index,
+                ..Default::default()
+            };
+        }
+        if x0 == x1 && y0 == y1 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x0 += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y0 += sy;
+        }
+    }
+}
+
+/// Flood fills every hidden (unclassified) cell reachable from `start`
+/// without crossing an already-painted (outline or previously filled) cell.
+fn flood_fill(start: Point, size: Size, index: usize, tiles: &mut [RawTile]) {
+    let width = size.width as usize;
+    let height = size.height as usize;
+    let start_index = match cell_index(start, size) {
+        Some(index) => index,
+        None => return,
+    };
+
+    let mut stack = vec![start_index];
+    while let Some(i) = stack.pop() {
+        if !tiles[i].is_hidden() {
+            continue;
+        }
+        tiles[i] = RawTile {
+            index,
+            ..Default::default()
+        };
+        let x = i % width;
+        let y = i / width;
+        if x > 0 {
+            stack.push(i - 1);
+        }
+        if x + 1 < width {
+            stack.push(i + 1);
+        }
+        if y > 0 {
+            stack.push(i - width);
+        }
+        if y + 1 < height {
+            stack.push(i + width);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_returns_none_for_an_empty_template_list() {
+        let mut rng = rand::thread_rng();
+        assert!(generate(&[], &mut rng).is_none());
+    }
+
+    #[test]
+    fn closed_square_outline_fills_its_interior_and_leaves_the_rest_hidden() {
+        let size = Size {
+            width: 5,
+            height: 5,
+        };
+        let mut tiles = vec![RawTile::default(); 25];
+        for tile in tiles.iter_mut() {
+            tile.hide();
+        }
+        let outline = vec![
+            Point { x: 1, y: 1 },
+            Point { x: 3, y: 1 },
+            Point { x: 3, y: 3 },
+            Point { x: 1, y: 3 },
+        ];
+
+        rasterize_outline(&outline, size, 9, &mut tiles);
+        flood_fill(Point { x: 2, y: 2 }, size, 9, &mut tiles);
+
+        for y in 1..=3 {
+            for x in 1..=3 {
+                let index = (y * size.width + x) as usize;
+                assert_eq!(tiles[index].index, 9);
+                assert!(!tiles[index].is_hidden());
+            }
+        }
+        assert!(tiles[0].is_hidden());
+    }
+
+    #[test]
+    fn jitter_offset_stays_within_range() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let offset = jitter_offset(&mut rng);
+            assert!(offset.x.abs() <= JITTER_RANGE);
+            assert!(offset.y.abs() <= JITTER_RANGE);
+        }
+    }
+}
@@ -2,6 +2,9 @@ use crate::{
     chunk::{SimpleTile, TileTrait},
     lib::*,
 };
+use nonmax::NonMaxUsize;
+#[cfg(feature = "serde")]
+use serde::{Deserializer, Serializer};
 
 /// Common methods for layers in a chunk.
 pub(super) trait Layer<T>: 'static
@@ -27,7 +30,7 @@ where
     fn clear(&mut self);
 
     /// Takes all the tiles in the layer and returns attributes for the renderer.
-    fn tiles_to_attributes(&self, dimension: Dimension3) -> (Vec<f32>, Vec<[f32; 4]>);
+    fn tiles_to_attributes(&self, dimension: Dimension3) -> (Vec<f32>, Vec<[f32; 4]>, Vec<f32>);
 }
 
 /// A layer with dense sprite tiles.
@@ -98,7 +101,7 @@ where
         self.tiles.clear();
     }
 
-    fn tiles_to_attributes(&self, _dimension: Dimension3) -> (Vec<f32>, Vec<[f32; 4]>) {
+    fn tiles_to_attributes(&self, _dimension: Dimension3) -> (Vec<f32>, Vec<[f32; 4]>, Vec<f32>) {
         crate::chunk::raw_tile::dense_tiles_to_attributes(&self.tiles)
     }
 }
@@ -117,11 +120,46 @@ where
 }
 
 /// A layer with sparse sprite tiles.
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+///
+/// Storage is a sparse set, as used by Bevy ECS: a dense, contiguous `Vec`
+/// holding the live `(tile index, tile)` pairs, plus a sparse `Vec` mapping a
+/// tile index to its slot in the dense vec. This keeps `tiles_to_attributes`
+/// and `get_tile_indices` walking contiguous memory instead of chasing a hash
+/// map's buckets. The dense vec is in insertion order until a tile is
+/// removed: removal moves the last tile into the removed tile's slot.
 #[derive(Clone, PartialEq, Debug)]
 pub(super) struct SparseLayer<T> {
-    /// A map of all the tiles in the chunk.
-    tiles: HashMap<usize, T>,
+    /// Contiguous storage of the live `(tile index, tile)` pairs.
+    dense: Vec<(usize, T)>,
+    /// Maps a tile index to its slot in `dense`, if the tile is live.
+    sparse: Vec<Option<NonMaxUsize>>,
+}
+
+#[cfg(feature = "serde")]
+impl<T> Serialize for SparseLayer<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.dense.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> Deserialize<'de> for SparseLayer<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let dense = Vec::<(usize, T)>::deserialize(deserializer)?;
+        Ok(SparseLayer::from_dense(dense))
+    }
 }
 
 impl<T> Layer<T> for SparseLayer<T>
@@ -130,49 +168,268 @@ where
 {
     fn set_tile(&mut self, index: usize, tile: T) {
         if tile.is_hidden() {
-            self.tiles.remove(&index);
+            self.remove_tile(index);
+            return;
         }
-        self.tiles.insert(index, tile);
+        if let Some(slot) = self.slot(index) {
+            self.dense[slot].1 = tile;
+            return;
+        }
+        if self.sparse.len() <= index {
+            self.sparse.resize(index + 1, None);
+        }
+        self.sparse[index] = NonMaxUsize::new(self.dense.len());
+        self.dense.push((index, tile));
     }
 
     fn remove_tile(&mut self, index: usize) {
-        self.tiles.remove(&index);
+        if let Some(slot) = self.slot(index) {
+            self.dense.swap_remove(slot);
+            if let Some((moved_index, _)) = self.dense.get(slot) {
+                self.sparse[*moved_index] = NonMaxUsize::new(slot);
+            }
+            self.sparse[index] = None;
+        }
     }
 
     fn get_tile(&self, index: usize) -> Option<&T> {
-        self.tiles.get(&index)
+        self.slot(index).map(|slot| &self.dense[slot].1)
     }
 
     fn get_tile_mut(&mut self, index: usize) -> Option<&mut T> {
-        self.tiles.get_mut(&index)
+        self.slot(index).map(move |slot| &mut self.dense[slot].1)
     }
 
     fn get_tile_indices(&self) -> Vec<usize> {
-        let mut indices = Vec::with_capacity(self.tiles.len());
-        for index in self.tiles.keys() {
-            indices.push(*index);
-        }
-        indices
+        self.dense.iter().map(|(index, _)| *index).collect()
     }
 
     fn clear(&mut self) {
-        self.tiles.clear();
+        self.dense.clear();
+        self.sparse.clear();
     }
 
-    fn tiles_to_attributes(&self, dimension: Dimension3) -> (Vec<f32>, Vec<[f32; 4]>) {
-        crate::chunk::raw_tile::sparse_tiles_to_attributes(dimension, &self.tiles)
+    fn tiles_to_attributes(&self, dimension: Dimension3) -> (Vec<f32>, Vec<[f32; 4]>, Vec<f32>) {
+        crate::chunk::raw_tile::sparse_tiles_to_attributes(dimension, &self.dense)
     }
 }
 
 impl<T> SparseLayer<T> {
     /// Constructs a new sparse layer with a tile hashmap.
     pub fn new(tiles: HashMap<usize, T>) -> SparseLayer<T> {
-        SparseLayer { tiles }
+        SparseLayer::from_dense(tiles.into_iter().collect())
+    }
+
+    /// Rebuilds a sparse layer's sparse index from a dense `(tile index, tile)`
+    /// list, as produced when deserializing.
+    fn from_dense(dense: Vec<(usize, T)>) -> SparseLayer<T> {
+        let mut sparse = Vec::new();
+        for (slot, (index, _)) in dense.iter().enumerate() {
+            if sparse.len() <= *index {
+                sparse.resize(*index + 1, None);
+            }
+            sparse[*index] = NonMaxUsize::new(slot);
+        }
+        SparseLayer { dense, sparse }
+    }
+
+    /// Looks up the dense-vec slot backing a tile index, if it is live.
+    fn slot(&self, index: usize) -> Option<usize> {
+        self.sparse.get(index).copied().flatten().map(NonMaxUsize::get)
+    }
+}
+
+/// The occupancy fraction above which an `Adaptive` layer rebuilds its
+/// sparse storage into dense storage.
+const ADAPTIVE_PROMOTE_THRESHOLD: f32 = 0.7;
+
+/// The occupancy fraction below which an `Adaptive` layer rebuilds its
+/// dense storage into sparse storage.
+///
+/// This is kept well below `ADAPTIVE_PROMOTE_THRESHOLD` so that a layer
+/// hovering near a single threshold does not rebuild on every tile change.
+const ADAPTIVE_DEMOTE_THRESHOLD: f32 = 0.3;
+
+/// A layer which automatically converts between dense and sparse storage
+/// as its occupancy changes.
+///
+/// Occupancy is tracked as the live tile count against the layer's area.
+/// Crossing `ADAPTIVE_PROMOTE_THRESHOLD` from below in `set_tile` rebuilds
+/// the sparse map into a dense vector; dropping below
+/// `ADAPTIVE_DEMOTE_THRESHOLD` in `remove_tile` rebuilds the dense vector back
+/// into a sparse map. Hiding tiles through `set_tile` does not demote.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, PartialEq, Debug)]
+pub(super) struct AdaptiveLayer<T>
+where
+    T: TileTrait + Default + Clone,
+{
+    /// The total number of tiles the layer can hold, used to compute occupancy.
+    area: usize,
+    /// The current storage backing the layer.
+    storage: AdaptiveStorage<T>,
+    /// The number of live (non-hidden) tiles currently in the layer, tracked
+    /// incrementally so `occupancy` doesn't need to rescan the storage.
+    live_count: usize,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, PartialEq, Debug)]
+enum AdaptiveStorage<T>
+where
+    T: TileTrait + Default + Clone,
+{
+    /// Backed by a dense layer.
+    Dense(DenseLayer<T>),
+    /// Backed by a sparse layer.
+    Sparse(SparseLayer<T>),
+}
+
+impl<T> AdaptiveLayer<T>
+where
+    T: TileTrait + Default + Clone,
+{
+    /// Constructs a new adaptive layer, starting out sparse, over an area of
+    /// `width * height` tiles.
+    pub fn new(dimension: Dimension3) -> AdaptiveLayer<T> {
+        AdaptiveLayer {
+            area: (dimension.width * dimension.height) as usize,
+            storage: AdaptiveStorage::Sparse(SparseLayer::new(HashMap::default())),
+            live_count: 0,
+        }
+    }
+
+    /// Returns whether the layer is currently backed by dense storage.
+    ///
+    /// Exposed so tests can observe a promote/demote transition without
+    /// reaching into the private `storage` field.
+    #[cfg(test)]
+    pub(crate) fn is_dense(&self) -> bool {
+        matches!(self.storage, AdaptiveStorage::Dense(_))
+    }
+
+    /// Returns the fraction of the layer's area currently occupied by tiles.
+    ///
+    /// Reads `live_count` rather than rescanning the storage, so this (and
+    /// the promote/demote check after every `set_tile`/`remove_tile`) stays
+    /// O(1) instead of O(area).
+    fn occupancy(&self) -> f32 {
+        self.live_count as f32 / self.area as f32
+    }
+
+    /// Rebuilds a sparse layer into a dense one by scattering its entries
+    /// into a `Vec` of `area` length.
+    fn promote(&mut self) {
+        if let AdaptiveStorage::Sparse(sparse) = &self.storage {
+            let mut tiles = Vec::with_capacity(self.area);
+            tiles.resize_with(self.area, || {
+                let mut tile = T::default();
+                tile.hide();
+                tile
+            });
+            let mut tile_count = 0;
+            for (index, tile) in sparse.dense.iter() {
+                if let Some(slot) = tiles.get_mut(*index) {
+                    *slot = tile.clone();
+                    tile_count += 1;
+                }
+            }
+            self.storage = AdaptiveStorage::Dense(DenseLayer { tiles, tile_count });
+        }
+    }
+
+    /// Rebuilds a dense layer into a sparse one by gathering its non-hidden
+    /// tiles into a `HashMap`.
+    fn demote(&mut self) {
+        if let AdaptiveStorage::Dense(dense) = &self.storage {
+            let mut tiles = HashMap::default();
+            for (index, tile) in dense.tiles.iter().enumerate() {
+                if !tile.is_hidden() {
+                    tiles.insert(index, tile.clone());
+                }
+            }
+            self.storage = AdaptiveStorage::Sparse(SparseLayer::new(tiles));
+        }
     }
 }
 
-/// Specifies which kind of layer to construct, either a dense or a sparse
-/// sprite layer.
+impl<T> Layer<T> for AdaptiveLayer<T>
+where
+    T: TileTrait + Default + Clone,
+{
+    fn set_tile(&mut self, index: usize, tile: T) {
+        let was_live = self.get_tile(index).is_some();
+        match &mut self.storage {
+            AdaptiveStorage::Dense(dense) => dense.set_tile(index, tile),
+            AdaptiveStorage::Sparse(sparse) => sparse.set_tile(index, tile),
+        }
+        let is_live = self.get_tile(index).is_some();
+        match (was_live, is_live) {
+            (false, true) => self.live_count += 1,
+            (true, false) => self.live_count -= 1,
+            _ => {}
+        }
+        if matches!(self.storage, AdaptiveStorage::Sparse(_))
+            && self.occupancy() > ADAPTIVE_PROMOTE_THRESHOLD
+        {
+            self.promote();
+        }
+    }
+
+    fn remove_tile(&mut self, index: usize) {
+        let was_live = self.get_tile(index).is_some();
+        match &mut self.storage {
+            AdaptiveStorage::Dense(dense) => dense.remove_tile(index),
+            AdaptiveStorage::Sparse(sparse) => sparse.remove_tile(index),
+        }
+        if was_live {
+            self.live_count -= 1;
+        }
+        if matches!(self.storage, AdaptiveStorage::Dense(_))
+            && self.occupancy() < ADAPTIVE_DEMOTE_THRESHOLD
+        {
+            self.demote();
+        }
+    }
+
+    fn get_tile(&self, index: usize) -> Option<&T> {
+        match &self.storage {
+            AdaptiveStorage::Dense(dense) => dense.get_tile(index),
+            AdaptiveStorage::Sparse(sparse) => sparse.get_tile(index),
+        }
+    }
+
+    fn get_tile_mut(&mut self, index: usize) -> Option<&mut T> {
+        match &mut self.storage {
+            AdaptiveStorage::Dense(dense) => dense.get_tile_mut(index),
+            AdaptiveStorage::Sparse(sparse) => sparse.get_tile_mut(index),
+        }
+    }
+
+    fn get_tile_indices(&self) -> Vec<usize> {
+        match &self.storage {
+            AdaptiveStorage::Dense(dense) => dense.get_tile_indices(),
+            AdaptiveStorage::Sparse(sparse) => sparse.get_tile_indices(),
+        }
+    }
+
+    fn clear(&mut self) {
+        match &mut self.storage {
+            AdaptiveStorage::Dense(dense) => dense.clear(),
+            AdaptiveStorage::Sparse(sparse) => sparse.clear(),
+        }
+        self.live_count = 0;
+    }
+
+    fn tiles_to_attributes(&self, dimension: Dimension3) -> (Vec<f32>, Vec<[f32; 4]>, Vec<f32>) {
+        match &self.storage {
+            AdaptiveStorage::Dense(dense) => dense.tiles_to_attributes(dimension),
+            AdaptiveStorage::Sparse(sparse) => sparse.tiles_to_attributes(dimension),
+        }
+    }
+}
+
+/// Specifies which kind of layer to construct: dense, sparse, or adaptive.
 ///
 /// The difference between a dense and sparse layer is namely the storage kind.
 /// A dense layer uses a vector and must fully contain tiles. This is ideal for
@@ -180,7 +437,10 @@ impl<T> SparseLayer<T> {
 /// to a tile. This is ideal for entities, objects or items.
 ///
 /// It is highly recommended to adhere to the above principles to get the lowest
-/// amount of byte usage.
+/// amount of byte usage. When a layer's occupancy is not known up front, or is
+/// expected to change significantly at runtime, use `Adaptive` instead: it
+/// starts out sparse and converts to dense storage (and back) on its own as
+/// tiles are set and removed.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub enum LayerKind {
@@ -188,49 +448,133 @@ pub enum LayerKind {
     Dense,
     /// Specifies the tilemap to add a sparse sprite layer.
     Sparse,
+    /// Specifies the tilemap to add a layer which converts between dense and
+    /// sparse storage automatically based on occupancy.
+    Adaptive,
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, PartialEq, Debug)]
-/// Inner enum used for storing either a dense or sparse layer.
+/// Inner enum used for storing either a dense, sparse, or adaptive layer.
 pub(super) enum LayerKindInner<T>
 where
-    T: TileTrait,
+    T: TileTrait + Default + Clone,
 {
     /// Inner dense layer storage.
     Dense(DenseLayer<T>),
     /// Inner sparse layer storage.
     Sparse(SparseLayer<T>),
+    /// Inner adaptive layer storage.
+    Adaptive(AdaptiveLayer<T>),
 }
 
 impl<T> AsRef<dyn Layer<T>> for LayerKindInner<T>
 where
-    T: TileTrait,
+    T: TileTrait + Default + Clone,
 {
     fn as_ref(&self) -> &dyn Layer<T> {
         match self {
             LayerKindInner::Dense(s) => s,
             LayerKindInner::Sparse(s) => s,
+            LayerKindInner::Adaptive(s) => s,
         }
     }
 }
 
 impl<T> AsMut<dyn Layer<T>> for LayerKindInner<T>
 where
-    T: TileTrait,
+    T: TileTrait + Default + Clone,
 {
     fn as_mut(&mut self) -> &mut dyn Layer<T> {
         match self {
             LayerKindInner::Dense(s) => s,
             LayerKindInner::Sparse(s) => s,
+            LayerKindInner::Adaptive(s) => s,
         }
     }
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, PartialEq, Debug)]
-/// A sprite layer which can either store a sparse or dense layer.
+/// A sprite layer which can either store a sparse, dense, or adaptive layer.
 pub(super) struct SpriteLayer {
     /// Enum storage of the kind of layer.
     pub inner: LayerKindInner<SimpleTile>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::raw_tile::RawTile;
+
+    fn tile(index: usize) -> RawTile {
+        RawTile {
+            index,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sparse_layer_swap_remove_fixes_up_moved_slot() {
+        let mut layer: SparseLayer<RawTile> = SparseLayer::new(HashMap::default());
+        layer.set_tile(1, tile(1));
+        layer.set_tile(2, tile(2));
+        layer.set_tile(3, tile(3));
+
+        // Removing index 1 swap-removes the last dense entry (index 3) into
+        // its slot; index 3 must still be reachable afterwards.
+        layer.remove_tile(1);
+        assert!(layer.get_tile(1).is_none());
+        assert_eq!(layer.get_tile(2).unwrap().index, 2);
+        assert_eq!(layer.get_tile(3).unwrap().index, 3);
+
+        layer.remove_tile(3);
+        assert!(layer.get_tile(3).is_none());
+        assert_eq!(layer.get_tile(2).unwrap().index, 2);
+    }
+
+    #[test]
+    fn sparse_layer_set_tile_twice_overwrites_in_place() {
+        let mut layer: SparseLayer<RawTile> = SparseLayer::new(HashMap::default());
+        layer.set_tile(5, tile(1));
+        layer.set_tile(5, tile(2));
+
+        assert_eq!(layer.get_tile_indices(), vec![5]);
+        assert_eq!(layer.get_tile(5).unwrap().index, 2);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn sparse_layer_serde_round_trips_through_the_dense_list() {
+        let mut layer: SparseLayer<RawTile> = SparseLayer::new(HashMap::default());
+        layer.set_tile(0, tile(7));
+        layer.set_tile(4, tile(9));
+
+        let json = serde_json::to_string(&layer).unwrap();
+        let restored: SparseLayer<RawTile> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.get_tile(0).unwrap().index, 7);
+        assert_eq!(restored.get_tile(4).unwrap().index, 9);
+        assert_eq!(restored.get_tile_indices().len(), 2);
+    }
+
+    #[test]
+    fn adaptive_layer_promotes_then_demotes_on_occupancy() {
+        let dimension = Dimension3 {
+            width: 10,
+            height: 10,
+            depth: 1,
+        };
+        let mut layer: AdaptiveLayer<RawTile> = AdaptiveLayer::new(dimension);
+
+        for i in 0..80 {
+            layer.set_tile(i, tile(i));
+        }
+        assert!(matches!(layer.storage, AdaptiveStorage::Dense(_)));
+
+        for i in 0..75 {
+            layer.remove_tile(i);
+        }
+        assert!(matches!(layer.storage, AdaptiveStorage::Sparse(_)));
+    }
+}
@@ -0,0 +1,119 @@
+mod generation;
+mod layer;
+mod raw_tile;
+mod render;
+mod tiled;
+
+use crate::lib::*;
+use layer::{AdaptiveLayer, DenseLayer, Layer, LayerKindInner, SparseLayer};
+
+pub use generation::{generate, generate_from_template, Point, Size, Template, TemplateRegion};
+pub use layer::LayerKind;
+pub use raw_tile::{RawTile, TileTrait, FLIP_D, FLIP_X, FLIP_Y};
+pub use render::{set_layer_mesh_attributes, ATTRIBUTE_TILE_COLOR, ATTRIBUTE_TILE_FLIP, ATTRIBUTE_TILE_INDEX};
+pub use tiled::{
+    decode_gids, object_layer_to_sparse, parse_tmx, tile_layer_to_dense,
+    tiled_layer_to_dense_or_sparse, ImportedLayer, TiledCompression, TiledEncoding, TiledLayer,
+    TiledLayerKind,
+};
+
+use layer::SpriteLayer;
+
+/// The tile type backing a chunk's sprite layers: an index, a tint, and
+/// orientation flags.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub(crate) struct SimpleTile {
+    /// The index of the tile in the sprite sheet.
+    pub index: usize,
+    /// The color, or tint, of the tile.
+    pub color: Color,
+    /// Packed `FLIP_X | FLIP_Y | FLIP_D` orientation flags.
+    pub flip_flags: u8,
+}
+
+impl TileTrait for SimpleTile {
+    fn get_color(&self) -> &Color {
+        &self.color
+    }
+
+    fn is_hidden(&self) -> bool {
+        self.color.a() == 0.0
+    }
+
+    fn hide(&mut self) {
+        self.color.set_a(0.0);
+    }
+
+    fn get_index(&self) -> usize {
+        self.index
+    }
+
+    fn get_flip_flags(&self) -> u8 {
+        self.flip_flags
+    }
+}
+
+impl SpriteLayer {
+    /// Builds a sprite layer of the requested `kind` over `dimension`,
+    /// starting from a fully-populated tile vector.
+    pub(crate) fn new(kind: LayerKind, tiles: Vec<SimpleTile>, dimension: Dimension3) -> SpriteLayer {
+        let inner = match kind {
+            LayerKind::Dense => LayerKindInner::Dense(DenseLayer::new(tiles)),
+            LayerKind::Sparse => {
+                let map = tiles
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(_, tile)| !tile.is_hidden())
+                    .collect();
+                LayerKindInner::Sparse(SparseLayer::new(map))
+            }
+            LayerKind::Adaptive => {
+                let mut adaptive = AdaptiveLayer::new(dimension);
+                for (index, tile) in tiles.into_iter().enumerate() {
+                    if !tile.is_hidden() {
+                        adaptive.set_tile(index, tile);
+                    }
+                }
+                LayerKindInner::Adaptive(adaptive)
+            }
+        };
+        SpriteLayer { inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adaptive_sprite_layer_promotes_then_demotes_through_the_public_api() {
+        let dimension = Dimension3 {
+            width: 10,
+            height: 10,
+            depth: 1,
+        };
+        let tiles = vec![SimpleTile::default(); 100];
+        let mut layer = SpriteLayer::new(LayerKind::Adaptive, tiles, dimension);
+        let adaptive = match &mut layer.inner {
+            LayerKindInner::Adaptive(adaptive) => adaptive,
+            _ => panic!("LayerKind::Adaptive must construct LayerKindInner::Adaptive"),
+        };
+
+        for i in 0..80 {
+            adaptive.set_tile(
+                i,
+                SimpleTile {
+                    index: i,
+                    ..Default::default()
+                },
+            );
+        }
+        assert!(adaptive.is_dense());
+
+        for i in 0..75 {
+            adaptive.remove_tile(i);
+        }
+        assert!(!adaptive.is_dense());
+    }
+}
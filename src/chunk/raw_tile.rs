@@ -1,20 +1,37 @@
 use crate::lib::*;
 
+/// Horizontal flip flag; set from bit 31 of a Tiled GID.
+pub const FLIP_X: u8 = 0b001;
+/// Vertical flip flag; set from bit 30 of a Tiled GID.
+pub const FLIP_Y: u8 = 0b010;
+/// Diagonal flip (anti-diagonal / 90-degree rotation) flag; set from bit 29
+/// of a Tiled GID.
+pub const FLIP_D: u8 = 0b100;
+
 pub trait TileTrait: 'static {
     fn get_color(&self) -> &Color;
     fn is_hidden(&self) -> bool;
     fn hide(&mut self);
     fn get_index(&self) -> usize;
+    /// Returns the tile's orientation as packed `FLIP_X | FLIP_Y | FLIP_D` flags.
+    ///
+    /// Defaults to no orientation so existing implementors keep compiling and
+    /// serialize identically to before this was added.
+    fn get_flip_flags(&self) -> u8 {
+        0
+    }
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Copy, Clone, PartialEq, Debug)]
-/// A raw tile composed of simply an index and a color.
+/// A raw tile composed of an index, a color, and orientation flags.
 pub struct RawTile {
     /// The index of the tile in the sprite sheet.
     pub index: usize,
     /// The color, or tint, of the tile.
     pub color: Color,
+    /// Packed `FLIP_X | FLIP_Y | FLIP_D` orientation flags.
+    pub flip_flags: u8,
 }
 
 impl Default for RawTile {
@@ -22,6 +39,7 @@ impl Default for RawTile {
         RawTile {
             index: 0,
             color: Color::WHITE,
+            flip_flags: 0,
         }
     }
 }
@@ -42,30 +60,38 @@ impl TileTrait for RawTile {
     fn get_index(&self) -> usize {
         self.index
     }
+
+    fn get_flip_flags(&self) -> u8 {
+        self.flip_flags
+    }
 }
 
-/// A utility function that takes an array of `Tile`s and splits the indexes and
-/// colors and returns them as separate vectors for use in the renderer.
-pub(crate) fn dense_tiles_to_attributes<T>(tiles: &[T]) -> (Vec<f32>, Vec<[f32; 4]>)
+/// A utility function that takes an array of `Tile`s and splits the indexes,
+/// colors and orientation flags and returns them as separate vectors for use
+/// in the renderer.
+pub(crate) fn dense_tiles_to_attributes<T>(tiles: &[T]) -> (Vec<f32>, Vec<[f32; 4]>, Vec<f32>)
 where
     T: TileTrait,
 {
     let capacity = tiles.len() * 4;
     let mut tile_indexes: Vec<f32> = Vec::with_capacity(capacity);
     let mut tile_colors: Vec<[f32; 4]> = Vec::with_capacity(capacity);
+    let mut tile_flips: Vec<f32> = Vec::with_capacity(capacity);
     for tile in tiles.iter() {
         tile_indexes.extend([tile.get_index() as f32; 4].iter());
         tile_colors.extend([(*tile.get_color()).into(); 4].iter());
+        tile_flips.extend([tile.get_flip_flags() as f32; 4].iter());
     }
-    (tile_indexes, tile_colors)
+    (tile_indexes, tile_colors, tile_flips)
 }
 
-/// A utility function that takes a sparse map of `Tile`s and splits the indexes
-/// and colors and returns them as separate vectors for use in the renderer.
+/// A utility function that takes a sparse-set's dense `(index, Tile)` list
+/// and splits the indexes, colors and orientation flags and returns them as
+/// separate vectors for use in the renderer.
 pub(crate) fn sparse_tiles_to_attributes<T>(
     dimension: Dimension3,
-    tiles: &HashMap<usize, T>,
-) -> (Vec<f32>, Vec<[f32; 4]>)
+    tiles: &[(usize, T)],
+) -> (Vec<f32>, Vec<[f32; 4]>, Vec<f32>)
 where
     T: TileTrait,
 {
@@ -73,6 +99,7 @@ where
     let mut tile_indexes = vec![0.; area * 4];
     // If tiles are set with an alpha of 0, they are discarded.
     let mut tile_colors = vec![[0.0, 0.0, 0.0, 0.0]; area * 4];
+    let mut tile_flips = vec![0.; area * 4];
     for (index, tile) in tiles.iter() {
         for i in 0..4 {
             if let Some(index) = tile_indexes.get_mut(index * 4 + i) {
@@ -81,7 +108,10 @@ where
             if let Some(index) = tile_colors.get_mut(index * 4 + i) {
                 *index = (*tile.get_color()).into();
             }
+            if let Some(index) = tile_flips.get_mut(index * 4 + i) {
+                *index = tile.get_flip_flags() as f32;
+            }
         }
     }
-    (tile_indexes, tile_colors)
+    (tile_indexes, tile_colors, tile_flips)
 }
@@ -0,0 +1,27 @@
+use crate::{
+    chunk::layer::{Layer, SpriteLayer},
+    lib::*,
+};
+
+/// Vertex attribute name for the per-vertex sprite-sheet index.
+pub const ATTRIBUTE_TILE_INDEX: &str = "Vertex_Tile_Index";
+/// Vertex attribute name for the per-vertex tint color.
+pub const ATTRIBUTE_TILE_COLOR: &str = "Vertex_Tile_Color";
+/// Vertex attribute name for the per-vertex flip/rotation flags.
+pub const ATTRIBUTE_TILE_FLIP: &str = "Vertex_Tile_Flip";
+
+/// Bakes a sprite layer's tiles into a chunk's mesh vertex attributes.
+///
+/// Writes the index and color channels alongside `ATTRIBUTE_TILE_FLIP`, the
+/// per-vertex orientation channel the shader reads to flip or rotate a
+/// sprite within its sheet.
+pub(crate) fn set_layer_mesh_attributes(
+    mesh: &mut Mesh,
+    layer: &SpriteLayer,
+    dimension: Dimension3,
+) {
+    let (indexes, colors, flips) = layer.inner.as_ref().tiles_to_attributes(dimension);
+    mesh.set_attribute(ATTRIBUTE_TILE_INDEX, indexes);
+    mesh.set_attribute(ATTRIBUTE_TILE_COLOR, colors);
+    mesh.set_attribute(ATTRIBUTE_TILE_FLIP, flips);
+}
@@ -0,0 +1,426 @@
+use crate::{
+    chunk::{
+        layer::{DenseLayer, SparseLayer},
+        raw_tile::{RawTile, FLIP_D, FLIP_X, FLIP_Y},
+    },
+    lib::*,
+};
+use std::io::Read;
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+/// How a Tiled `<data>` element encodes its tile GIDs.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum TiledEncoding {
+    /// GIDs are stored as a comma separated list of decimal numbers.
+    Csv,
+    /// GIDs are stored as base64 text, optionally compressed.
+    Base64,
+}
+
+/// How a base64-encoded `<data>` element is compressed.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum TiledCompression {
+    /// No compression; the decoded bytes are the raw GIDs.
+    None,
+    /// Gzip compression.
+    Gzip,
+    /// Zlib compression.
+    Zlib,
+}
+
+/// A single Tiled layer, decoded down to its flat array of global tile IDs.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TiledLayer {
+    /// Whether this layer came from a Tiled tile layer or an object layer.
+    pub kind: TiledLayerKind,
+    /// The layer's width in tiles.
+    pub width: u32,
+    /// The layer's height in tiles.
+    pub height: u32,
+    /// The decoded global tile IDs, in Tiled's row-major order.
+    pub gids: Vec<u32>,
+}
+
+/// Which kind of Tiled layer a `TiledLayer` was parsed from.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum TiledLayerKind {
+    /// A `<layer>` element; fully populated, so it becomes a `DenseLayer`.
+    Tile,
+    /// An `<objectgroup>` element; sparsely populated, so it becomes a
+    /// `SparseLayer`.
+    Object,
+}
+
+/// The low 28 bits of a Tiled global tile ID select the sprite-sheet index;
+/// the top 4 bits are flag bits, of which `gid_to_flip_flags` reads the
+/// horizontal, vertical and diagonal flips (bit 28 is ignored).
+const GID_INDEX_MASK: u32 = 0x0FFF_FFFF;
+
+const GID_FLIP_HORIZONTAL: u32 = 0x8000_0000;
+const GID_FLIP_VERTICAL: u32 = 0x4000_0000;
+const GID_FLIP_DIAGONAL: u32 = 0x2000_0000;
+
+/// Extracts the sprite-sheet index encoded in a Tiled global tile ID.
+pub(crate) fn gid_to_index(gid: u32) -> usize {
+    (gid & GID_INDEX_MASK) as usize
+}
+
+/// Extracts the horizontal/vertical/diagonal flip flags packed into a
+/// Tiled global tile ID's top bits, as `RawTile::flip_flags`.
+pub(crate) fn gid_to_flip_flags(gid: u32) -> u8 {
+    let mut flags = 0;
+    if gid & GID_FLIP_HORIZONTAL != 0 {
+        flags |= FLIP_X;
+    }
+    if gid & GID_FLIP_VERTICAL != 0 {
+        flags |= FLIP_Y;
+    }
+    if gid & GID_FLIP_DIAGONAL != 0 {
+        flags |= FLIP_D;
+    }
+    flags
+}
+
+/// Parses every `<layer>` and `<objectgroup>` element out of a Tiled TMX
+/// document.
+///
+/// Object layers have no `<data>` grid of their own: each placed tile object
+/// is a separate `<object gid="..." x="..." y="...">`, anchored at the
+/// bottom-left of its cell in pixel space. Those are rasterized onto a grid
+/// the size of the map (taken from the enclosing `<map>` element) using its
+/// `tilewidth`/`tileheight`, so the result lines up with the tile layers.
+///
+/// A tile layer whose `<data>` does not decode to exactly `width * height`
+/// GIDs is skipped with a warning.
+pub fn parse_tmx(tmx: &str) -> Vec<TiledLayer> {
+    let mut reader = Reader::from_str(tmx);
+    reader.trim_text(true);
+
+    let mut layers = Vec::new();
+    let mut map_width = 0;
+    let mut map_height = 0;
+    let mut tile_width = 1;
+    let mut tile_height = 1;
+
+    let mut kind = None;
+    let mut width = 0;
+    let mut height = 0;
+    let mut encoding = TiledEncoding::Csv;
+    let mut compression = TiledCompression::None;
+    let mut object_gids: Vec<u32> = Vec::new();
+    let mut in_data = false;
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => match e.name() {
+                b"map" => {
+                    map_width = attr_u32(e, b"width").unwrap_or(0);
+                    map_height = attr_u32(e, b"height").unwrap_or(0);
+                    tile_width = attr_u32(e, b"tilewidth").unwrap_or(1).max(1);
+                    tile_height = attr_u32(e, b"tileheight").unwrap_or(1).max(1);
+                }
+                b"layer" => {
+                    kind = Some(TiledLayerKind::Tile);
+                    width = attr_u32(e, b"width").unwrap_or(map_width);
+                    height = attr_u32(e, b"height").unwrap_or(map_height);
+                }
+                b"objectgroup" => {
+                    kind = Some(TiledLayerKind::Object);
+                    width = map_width;
+                    height = map_height;
+                    object_gids = vec![0; (width * height) as usize];
+                }
+                b"data" => {
+                    in_data = true;
+                    encoding = match attr_str(e, b"encoding").as_deref() {
+                        Some("base64") => TiledEncoding::Base64,
+                        _ => TiledEncoding::Csv,
+                    };
+                    compression = match attr_str(e, b"compression").as_deref() {
+                        Some("gzip") => TiledCompression::Gzip,
+                        Some("zlib") => TiledCompression::Zlib,
+                        _ => TiledCompression::None,
+                    };
+                }
+                b"object" if kind == Some(TiledLayerKind::Object) => {
+                    if let Some(gid) = attr_u32(e, b"gid") {
+                        let x = attr_f32(e, b"x").unwrap_or(0.0);
+                        let y = attr_f32(e, b"y").unwrap_or(0.0);
+                        let col = (x / tile_width as f32) as u32;
+                        // Tile objects anchor at the bottom-left of their cell.
+                        let row = (y / tile_height as f32).floor() as u32;
+                        let row = row.saturating_sub(1);
+                        if col < width && row < height {
+                            if let Some(slot) = object_gids.get_mut((row * width + col) as usize) {
+                                *slot = gid;
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            },
+            Ok(Event::Text(e)) => {
+                // Gated on in_data, not just kind == Tile, so whitespace text
+                // nodes between <layer> and <data> (or after it) can't push
+                // a spurious layer.
+                if in_data && kind == Some(TiledLayerKind::Tile) {
+                    let text = e.unescape_and_decode(&reader).unwrap_or_default();
+                    if !text.trim().is_empty() {
+                        let gids = decode_gids(&text, encoding, compression);
+                        let expected = (width * height) as usize;
+                        if gids.len() == expected {
+                            layers.push(TiledLayer {
+                                kind: TiledLayerKind::Tile,
+                                width,
+                                height,
+                                gids,
+                            });
+                        } else {
+                            warn!(
+                                "tile layer data decoded to {} gids, expected {} ({}x{}); skipping layer",
+                                gids.len(),
+                                expected,
+                                width,
+                                height
+                            );
+                        }
+                    }
+                }
+            }
+            Ok(Event::End(ref e)) => match e.name() {
+                b"data" => in_data = false,
+                b"layer" => kind = None,
+                b"objectgroup" => {
+                    if kind == Some(TiledLayerKind::Object) {
+                        layers.push(TiledLayer {
+                            kind: TiledLayerKind::Object,
+                            width,
+                            height,
+                            gids: std::mem::take(&mut object_gids),
+                        });
+                    }
+                    kind = None;
+                }
+                _ => {}
+            },
+            Ok(Event::Eof) => break,
+            Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    layers
+}
+
+fn attr_str(e: &quick_xml::events::BytesStart, key: &[u8]) -> Option<String> {
+    e.attributes()
+        .filter_map(Result::ok)
+        .find(|a| a.key == key)
+        .map(|a| String::from_utf8_lossy(&a.value).into_owned())
+}
+
+fn attr_u32(e: &quick_xml::events::BytesStart, key: &[u8]) -> Option<u32> {
+    attr_str(e, key).and_then(|s| s.parse().ok())
+}
+
+fn attr_f32(e: &quick_xml::events::BytesStart, key: &[u8]) -> Option<f32> {
+    attr_str(e, key).and_then(|s| s.parse().ok())
+}
+
+/// Decodes a `<data>` element's text into a flat array of global tile IDs.
+///
+/// A GID of `0` means the cell has no tile.
+pub fn decode_gids(data: &str, encoding: TiledEncoding, compression: TiledCompression) -> Vec<u32> {
+    match encoding {
+        TiledEncoding::Csv => {
+            let mut fields: Vec<&str> = data.split(',').map(str::trim).collect();
+            // Tiled's own CSV encoder leaves a trailing comma after the last
+            // value, so the final field is expected to be empty; drop just
+            // that one rather than treating it as a malformed cell.
+            if fields.last() == Some(&"") {
+                fields.pop();
+            }
+            // Any other field that fails to parse (including an empty one
+            // from a stray comma) becomes GID 0 rather than being dropped,
+            // so a malformed row can't shift every tile after it out of
+            // alignment with the rest of the grid.
+            fields
+                .into_iter()
+                .map(|s| s.parse::<u32>().unwrap_or(0))
+                .collect()
+        }
+        TiledEncoding::Base64 => {
+            let bytes = base64::decode(data.trim()).unwrap_or_default();
+            let bytes = match compression {
+                TiledCompression::None => bytes,
+                TiledCompression::Gzip => inflate(GzDecoder::new(&bytes[..])),
+                TiledCompression::Zlib => inflate(ZlibDecoder::new(&bytes[..])),
+            };
+            bytes
+                .chunks_exact(4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect()
+        }
+    }
+}
+
+fn inflate<R: Read>(mut decoder: R) -> Vec<u8> {
+    let mut out = Vec::new();
+    decoder.read_to_end(&mut out).unwrap_or_default();
+    out
+}
+
+/// Builds a fully-populated dense layer from a Tiled tile layer's GIDs.
+///
+/// `gids` must be `width * height` long, in Tiled's row-major
+/// (left-to-right, top-to-bottom) order, which matches the `Layer` trait's
+/// flat `usize` indexing.
+pub fn tile_layer_to_dense(gids: &[u32]) -> DenseLayer<RawTile> {
+    let tiles = gids
+        .iter()
+        .map(|&gid| {
+            let mut tile = RawTile {
+                index: gid_to_index(gid),
+                flip_flags: gid_to_flip_flags(gid),
+                ..Default::default()
+            };
+            if gid == 0 {
+                tile.hide();
+            }
+            tile
+        })
+        .collect();
+    DenseLayer::new(tiles)
+}
+
+/// Builds a sparse layer from a Tiled object layer's GIDs, skipping empty
+/// cells (GID `0`) entirely.
+pub fn object_layer_to_sparse(gids: &[u32]) -> SparseLayer<RawTile> {
+    let mut tiles = HashMap::default();
+    for (index, &gid) in gids.iter().enumerate() {
+        if gid != 0 {
+            tiles.insert(
+                index,
+                RawTile {
+                    index: gid_to_index(gid),
+                    flip_flags: gid_to_flip_flags(gid),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+    SparseLayer::new(tiles)
+}
+
+/// The crate layer storage a `TiledLayer` converts into: tile layers become
+/// `DenseLayer`, object layers become `SparseLayer`.
+pub enum ImportedLayer {
+    /// A fully-populated tile layer.
+    Dense(DenseLayer<RawTile>),
+    /// A sparsely-populated object layer.
+    Sparse(SparseLayer<RawTile>),
+}
+
+/// Converts a parsed `TiledLayer` into the matching crate layer storage.
+pub fn tiled_layer_to_dense_or_sparse(layer: &TiledLayer) -> ImportedLayer {
+    match layer.kind {
+        TiledLayerKind::Tile => ImportedLayer::Dense(tile_layer_to_dense(&layer.gids)),
+        TiledLayerKind::Object => ImportedLayer::Sparse(object_layer_to_sparse(&layer.gids)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    #[test]
+    fn decode_gids_csv() {
+        let gids = decode_gids("1,2,3,0", TiledEncoding::Csv, TiledCompression::None);
+        assert_eq!(gids, vec![1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn decode_gids_csv_drops_only_the_trailing_comma() {
+        let gids = decode_gids("1,2,\n3,4,\n5,6,", TiledEncoding::Csv, TiledCompression::None);
+        assert_eq!(gids, vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn decode_gids_csv_maps_malformed_fields_to_zero_instead_of_dropping_them() {
+        let gids = decode_gids("1,,3", TiledEncoding::Csv, TiledCompression::None);
+        assert_eq!(gids, vec![1, 0, 3]);
+    }
+
+    #[test]
+    fn decode_gids_base64_zlib_round_trips() {
+        let original: Vec<u32> = vec![5, 0, 9, 1];
+        let mut bytes = Vec::new();
+        for gid in &original {
+            bytes.extend_from_slice(&gid.to_le_bytes());
+        }
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&bytes).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let encoded = base64::encode(&compressed);
+
+        let gids = decode_gids(&encoded, TiledEncoding::Base64, TiledCompression::Zlib);
+        assert_eq!(gids, original);
+    }
+
+    #[test]
+    fn gid_to_index_and_flip_flags_split_the_top_bits() {
+        let gid = 5 | 0x8000_0000 | 0x2000_0000;
+        assert_eq!(gid_to_index(gid), 5);
+        assert_eq!(gid_to_flip_flags(gid), FLIP_X | FLIP_D);
+    }
+
+    #[test]
+    fn parse_tmx_populates_object_layer_from_object_elements() {
+        let tmx = r#"<map width="2" height="2" tilewidth="16" tileheight="16">
+            <objectgroup>
+                <object id="1" gid="7" x="16" y="32"/>
+            </objectgroup>
+        </map>"#;
+
+        let layers = parse_tmx(tmx);
+
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].kind, TiledLayerKind::Object);
+        // x=16,y=32 with 16px tiles lands in column 1, row 1 (objects anchor
+        // at the bottom-left of their cell), i.e. flat index 3 of a 2-wide grid.
+        assert_eq!(layers[0].gids, vec![0, 0, 0, 7]);
+    }
+
+    #[test]
+    fn parse_tmx_populates_tile_layer_from_a_real_layer_element() {
+        let tmx = r#"<map width="2" height="2" tilewidth="16" tileheight="16">
+            <layer width="2" height="2">
+                <data encoding="csv">1,2,3,4</data>
+            </layer>
+        </map>"#;
+
+        let layers = parse_tmx(tmx);
+
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].kind, TiledLayerKind::Tile);
+        assert_eq!(layers[0].gids, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn parse_tmx_ignores_whitespace_between_layer_and_data() {
+        let tmx = "<map width=\"2\" height=\"2\" tilewidth=\"16\" tileheight=\"16\">\n            <layer width=\"2\" height=\"2\">\n                \n                <data encoding=\"csv\">1,2,3,4</data>\n            </layer>\n        </map>";
+
+        let layers = parse_tmx(tmx);
+
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].gids, vec![1, 2, 3, 4]);
+    }
+}